@@ -0,0 +1,95 @@
+/// A pluggable audio compression scheme, operating on the app's fixed f32
+/// sample buffer.
+pub trait Codec {
+    fn name(&self) -> &str;
+    fn encode(&self, samples: &[f32]) -> Vec<u8>;
+    fn decode(&self, bytes: &[u8]) -> Vec<f32>;
+}
+
+/// Delta-encodes each sample against the previous one, quantized to i16.
+/// The LSB of the first byte picks between a compact 1-byte symbol (small
+/// deltas) and a full 2-byte symbol (large deltas).
+pub struct DeltaLsbCodec;
+
+impl Codec for DeltaLsbCodec {
+    fn name(&self) -> &str {
+        "Delta LSB"
+    }
+
+    fn encode(&self, samples: &[f32]) -> Vec<u8> {
+        let mut new_buffer = Vec::new();
+        let mut last = 0.0;
+        for byte in samples.iter() {
+            let byte1 = *byte as f64 * i16::MAX as f64;
+            let byte1 = (byte1 - (last as f64 * i16::MAX as f64)) as i16;
+            last = *byte;
+            if byte1 < i8::MAX as i16 && (i8::MIN as i16) < byte1 {
+                println!("pbyte: {byte1}");
+                let mut byte1 = byte1 as u8;
+                if byte1 & 1 == 1 {
+                    byte1 -= 1;
+                }
+                new_buffer.push(byte1)
+            } else {
+                println!("nbyte: {byte1}");
+                let mut bytes = byte1.to_le_bytes();
+                if bytes[0] & 1 == 0 {
+                    bytes[0] += 1;
+                }
+                new_buffer.extend(bytes);
+            }
+        }
+        println!("Encoded: size {}", new_buffer.len());
+        new_buffer
+    }
+
+    fn decode(&self, bytes: &[u8]) -> Vec<f32> {
+        let mut new_buffer = Vec::new();
+        let mut last = 0f32;
+        let mut iter = bytes.iter().copied();
+        while let Some(byte) = iter.next() {
+            println!("Byte: {byte:8b}");
+            if byte & 1 == 1 {
+                if let Some(seccond_byte) = iter.next() {
+                    let byte = i16::from_le_bytes([byte, seccond_byte]) as f64 / i16::MAX as f64;
+                    let byte = byte + last as f64;
+                    let byte = byte as f32;
+                    last = byte;
+                    new_buffer.push(byte);
+                }
+            } else {
+                let byte = (byte as i8) as f64 / i16::MAX as f64;
+                let byte = byte + last as f64;
+                let byte = byte as f32;
+                last = byte;
+                new_buffer.push(byte);
+            }
+        }
+        println!(
+            "Decoded: size {}",
+            new_buffer.len() * std::mem::size_of::<f32>()
+        );
+        new_buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delta_lsb_round_trip() {
+        let samples: Vec<f32> = (0..20)
+            .map(|i| (i as f64 * 0.1).sin() as f32 * 0.5)
+            .collect();
+        let codec = DeltaLsbCodec;
+        let decoded = codec.decode(&codec.encode(&samples));
+        assert_eq!(decoded.len(), samples.len());
+        for (input, output) in samples.iter().zip(decoded.iter()) {
+            assert!(
+                (input - output).abs() < 0.01,
+                "expected {input}, got {output}"
+            );
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::{fmt, path::Path};
+
+use ffmpeg_next::{
+    format::{self, sample::Sample},
+    media::Type,
+    software::resampling::context::Context as ResamplingContext,
+    ChannelLayout,
+};
+
+#[derive(Debug)]
+pub enum DecodeError {
+    Ffmpeg(ffmpeg_next::Error),
+    NoAudioStream,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Ffmpeg(error) => write!(f, "ffmpeg error: {error}"),
+            DecodeError::NoAudioStream => write!(f, "file has no audio stream"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<ffmpeg_next::Error> for DecodeError {
+    fn from(error: ffmpeg_next::Error) -> Self {
+        DecodeError::Ffmpeg(error)
+    }
+}
+
+/// Demuxes, decodes and resamples the first audio stream of `path` into
+/// interleaved f32 samples at `target_channels`/`target_rate`, ready to be
+/// pushed into `Application::buffer`.
+pub fn decode_file(
+    path: &Path,
+    target_channels: u16,
+    target_rate: u32,
+) -> Result<Vec<f32>, DecodeError> {
+    let mut input = format::input(path)?;
+
+    let stream = input
+        .streams()
+        .best(Type::Audio)
+        .ok_or(DecodeError::NoAudioStream)?;
+    let stream_index = stream.index();
+
+    let context = ffmpeg_next::codec::context::Context::from_parameters(stream.parameters())?;
+    let mut decoder = context.decoder().audio()?;
+
+    let target_layout = ChannelLayout::default(target_channels as i32);
+
+    let mut resampler = decoder.resampler(
+        Sample::F32(format::sample::Type::Packed),
+        target_layout,
+        target_rate,
+    )?;
+
+    let mut samples = Vec::new();
+    let mut decoded = ffmpeg_next::frame::Audio::empty();
+    let mut resampled = ffmpeg_next::frame::Audio::empty();
+
+    for (stream, packet) in input.packets() {
+        if stream.index() != stream_index {
+            continue;
+        }
+        decoder.send_packet(&packet)?;
+        while decoder.receive_frame(&mut decoded).is_ok() {
+            resample_into(&mut resampler, &decoded, &mut resampled, &mut samples)?;
+        }
+    }
+
+    decoder.send_eof()?;
+    while decoder.receive_frame(&mut decoded).is_ok() {
+        resample_into(&mut resampler, &decoded, &mut resampled, &mut samples)?;
+    }
+
+    // The resampler buffers a few hundred samples internally; feed it a
+    // final empty frame and keep draining until it has nothing left, or
+    // the tail of the audio is silently dropped.
+    loop {
+        let delay = resampler.run(&ffmpeg_next::frame::Audio::empty(), &mut resampled)?;
+        push_samples(&resampled, &mut samples);
+        if delay.is_none() {
+            break;
+        }
+    }
+
+    Ok(samples)
+}
+
+fn resample_into(
+    resampler: &mut ResamplingContext,
+    input: &ffmpeg_next::frame::Audio,
+    output: &mut ffmpeg_next::frame::Audio,
+    samples: &mut Vec<f32>,
+) -> Result<(), DecodeError> {
+    resampler.run(input, output)?;
+    push_samples(output, samples);
+    Ok(())
+}
+
+fn push_samples(frame: &ffmpeg_next::frame::Audio, samples: &mut Vec<f32>) {
+    if frame.samples() == 0 {
+        return;
+    }
+    let channels = frame.channel_layout().channels() as usize;
+    let sample_size = std::mem::size_of::<f32>();
+    let data = &frame.data(0)[..frame.samples() * channels * sample_size];
+    samples.extend(
+        data.chunks_exact(sample_size)
+            .map(|bytes| f32::from_ne_bytes(bytes.try_into().unwrap())),
+    );
+}
@@ -1,4 +1,4 @@
-use std::{collections::VecDeque, sync::mpsc};
+use std::sync::{mpsc, Arc, Mutex};
 
 use cpal::{
     traits::{DeviceTrait, HostTrait, StreamTrait},
@@ -6,20 +6,66 @@ use cpal::{
 };
 use eframe::{egui, App};
 
+mod codec;
+mod decoder;
+mod pcm_buffers;
+mod wav;
+
+use codec::{Codec, DeltaLsbCodec};
+use pcm_buffers::PcmBuffers;
+use wav::WavFormat;
+
+/// Number of frames each playback refill tops `output_buffers` up to.
+const PLAYBACK_CHUNK_FRAMES: usize = 4096;
+
+/// Producer-side state driving seamless intro+loop playback: which region
+/// is currently being fed into `output_buffers` and how far into it we are.
+struct PlaybackState {
+    playing_intro: bool,
+    position: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterpolationMode {
+    Linear,
+    CatmullRom,
+}
+
+impl InterpolationMode {
+    const ALL: [InterpolationMode; 2] = [InterpolationMode::Linear, InterpolationMode::CatmullRom];
+
+    fn name(&self) -> &'static str {
+        match self {
+            InterpolationMode::Linear => "Linear",
+            InterpolationMode::CatmullRom => "Catmull-Rom",
+        }
+    }
+}
+
 pub struct Application {
     host: Host,
     output_device: Device,
     output_device_config: SupportedStreamConfig,
     output_stream: Stream,
-    output_sender: mpsc::SyncSender<Vec<f32>>,
-    output_rem_receiver: mpsc::Receiver<usize>,
+    output_buffers: Arc<Mutex<PcmBuffers>>,
     input_stream: Stream,
+    input_channels: u16,
+    input_sample_rate: u32,
     input_receiver: mpsc::Receiver<Vec<f32>>,
     input_sender: mpsc::SyncSender<bool>,
 
+    /// Channel count and sample rate `buffer` is interleaved at; taken from
+    /// the output device's own supported config instead of a hard-coded
+    /// mono 48 kHz layout.
+    channels: u16,
+    sample_rate: u32,
+
     buffers: Vec<Vec<f32>>,
     buffer: Vec<f32>,
     encoded_buffer: Vec<u8>,
+    codecs: Vec<Box<dyn Codec>>,
+    selected_codec: usize,
+    codec_sizes: Vec<(String, usize)>,
 
     recording: bool,
 
@@ -28,6 +74,12 @@ pub struct Application {
     offset: usize,
     length: usize,
     speed: f32,
+    interpolation_mode: InterpolationMode,
+    wav_bit_depth: u16,
+    loop_enabled: bool,
+    intro_start: usize,
+    loop_start: usize,
+    playback: Option<PlaybackState>,
 
     resolution: usize,
 }
@@ -38,35 +90,33 @@ impl Default for Application {
 
         let output_device = host.default_output_device().unwrap();
         let output_device_config = output_device.default_output_config().unwrap();
+        let channels = output_device_config.channels();
+        let sample_rate = output_device_config.sample_rate().0;
 
         let input_device = host.default_input_device().unwrap();
+        let input_device_config = input_device.default_input_config().unwrap();
+        let input_channels = input_device_config.channels();
+        let input_sample_rate = input_device_config.sample_rate().0;
 
-        let (output_sender, output_receiver) = mpsc::sync_channel::<Vec<f32>>(16);
-        let (output_rem_sender, output_rem_receiver) = mpsc::sync_channel::<usize>(16);
         let (input_sender, input_receiver) = mpsc::sync_channel::<Vec<f32>>(16);
         let (input_sender_rec, input_receiver_rec) = mpsc::sync_channel(16);
 
+        let output_buffers = Arc::new(Mutex::new(PcmBuffers::default()));
+
         let output_stream = {
-            let mut buffer = VecDeque::new();
+            let output_buffers = output_buffers.clone();
             output_device
                 .build_output_stream(
                     &cpal::StreamConfig {
-                        channels: 1,
-                        sample_rate: SampleRate(48000),
+                        channels,
+                        sample_rate: SampleRate(sample_rate),
                         buffer_size: cpal::BufferSize::Default,
                     },
                     move |data: &mut [f32], _| {
-                        while let Ok(buff) = output_receiver.try_recv() {
-                            buffer.extend(buff);
-                        }
-                        for byte in data.iter_mut() {
-                            *byte = if let Some(b) = buffer.pop_front() {
-                                b
-                            } else {
-                                0.0
-                            }
+                        let mut bufs = output_buffers.lock().unwrap();
+                        if !bufs.consume_exact(data) {
+                            data.fill(0.0);
                         }
-                        let _ = output_rem_sender.send(buffer.len());
                     },
                     |error| eprintln!("Output stream Error: {error}"),
                     None,
@@ -79,8 +129,8 @@ impl Default for Application {
             input_device
                 .build_input_stream(
                     &cpal::StreamConfig {
-                        channels: 1,
-                        sample_rate: SampleRate(48000),
+                        channels: input_channels,
+                        sample_rate: SampleRate(input_sample_rate),
                         buffer_size: cpal::BufferSize::Default,
                     },
                     move |data: &[f32], _| {
@@ -102,13 +152,22 @@ impl Default for Application {
             output_device,
             output_device_config,
             output_stream,
-            output_sender,
-            output_rem_receiver,
+            output_buffers,
+            input_channels,
+            input_sample_rate,
+            channels,
+            sample_rate,
             rem: 0,
             offset: 0,
             length: 48 * 20,
             buffer: vec![],
             speed: 1.0,
+            interpolation_mode: InterpolationMode::Linear,
+            wav_bit_depth: 16,
+            loop_enabled: false,
+            intro_start: 0,
+            loop_start: 0,
+            playback: None,
             frequency: 0.1,
             recording: false,
             input_stream,
@@ -117,18 +176,85 @@ impl Default for Application {
             resolution: 1000,
             encoded_buffer: vec![],
             buffers: vec![],
+            codecs: vec![Box::new(DeltaLsbCodec)],
+            selected_codec: 0,
+            codec_sizes: vec![],
+        }
+    }
+}
+
+impl Application {
+    /// Tops up `output_buffers` from the current playback region, switching
+    /// from the intro slice to repeatedly re-queuing the loop slice once the
+    /// intro has drained.
+    ///
+    /// The frames to queue are assembled into a single local `Vec` first and
+    /// `output_buffers` is locked only to read the current fill level and
+    /// once more to push the result, so a short loop region doesn't turn
+    /// into thousands of one-frame allocations fighting the audio callback
+    /// for the same lock.
+    fn pump_playback(&mut self) {
+        let Some(mut state) = self.playback.take() else {
+            return;
+        };
+
+        let channels = self.channels.max(1) as usize;
+        let frame_count = self.buffer.len() / channels;
+        let loop_start = self.loop_start.min(frame_count);
+
+        let available_frames = self.output_buffers.lock().unwrap().samples_available() / channels;
+        let needed_frames = PLAYBACK_CHUNK_FRAMES.saturating_sub(available_frames);
+
+        let mut chunk = Vec::with_capacity(needed_frames * channels);
+        let mut stopped = false;
+        while chunk.len() < needed_frames * channels {
+            let region_end = if state.playing_intro {
+                loop_start
+            } else {
+                frame_count
+            };
+
+            if state.position >= region_end {
+                if loop_start >= frame_count {
+                    // Nothing left to loop over; stop feeding.
+                    stopped = true;
+                    break;
+                }
+                state.playing_intro = false;
+                state.position = loop_start;
+                continue;
+            }
+
+            let frames_left_in_chunk = needed_frames - chunk.len() / channels;
+            let frames_to_take = frames_left_in_chunk.min(region_end - state.position);
+            let slice_end = state.position + frames_to_take;
+            chunk.extend_from_slice(&self.buffer[state.position * channels..slice_end * channels]);
+            state.position = slice_end;
+        }
+
+        if !chunk.is_empty() {
+            self.output_buffers.lock().unwrap().produce(chunk);
+        }
+
+        if !stopped {
+            self.playback = Some(state);
         }
     }
 }
 
 impl App for Application {
     fn update(&mut self, ctx: &eframe::egui::Context, frame: &mut eframe::Frame) {
-        while let Ok(rem) = self.output_rem_receiver.try_recv() {
-            self.rem = rem;
-        }
+        self.pump_playback();
+        self.rem = self.output_buffers.lock().unwrap().samples_available();
         while let Ok(buff) = self.input_receiver.try_recv() {
             if self.recording {
-                self.buffer.extend(buff)
+                self.buffer.extend(convert_audio(
+                    &buff,
+                    self.input_channels,
+                    self.input_sample_rate,
+                    self.channels,
+                    self.sample_rate,
+                ));
             }
         }
         egui::TopBottomPanel::bottom("Controls").show(ctx, |ui| {
@@ -142,6 +268,13 @@ impl App for Application {
                         .speed(0.00001)
                         .clamp_range(0..=1),
                 );
+                egui::ComboBox::from_label("Interpolation")
+                    .selected_text(self.interpolation_mode.name())
+                    .show_ui(ui, |ui| {
+                        for mode in InterpolationMode::ALL {
+                            ui.selectable_value(&mut self.interpolation_mode, mode, mode.name());
+                        }
+                    });
             });
 
             if ui.checkbox(&mut self.recording, "Recording").changed() {
@@ -149,102 +282,210 @@ impl App for Application {
             }
 
             if ui.button("Sin").clicked() {
-                self.buffer.resize(self.buffer.len().max(self.length), 0.0);
+                let channels = self.channels as usize;
+                self.buffer
+                    .resize(self.buffer.len().max(self.length * channels), 0.0);
                 for i in 0..self.length {
                     let sample = i as f32;
                     let sample = (sample as f64 * self.frequency).sin() as f32 * 0.5;
-                    self.buffer[i] += sample;
+                    for c in 0..channels {
+                        self.buffer[i * channels + c] += sample;
+                    }
                 }
             }
 
+            egui::ComboBox::from_label("Codec")
+                .selected_text(self.codecs[self.selected_codec].name())
+                .show_ui(ui, |ui| {
+                    for (i, codec) in self.codecs.iter().enumerate() {
+                        ui.selectable_value(&mut self.selected_codec, i, codec.name());
+                    }
+                });
+
             if ui.button("Encode").clicked() {
-                let mut new_buffer = Vec::new();
-                let mut last = 0.0;
-                for byte in self.buffer.iter() {
-                    let byte1 = *byte as f64 * i16::MAX as f64;
-                    let byte1 = (byte1 - (last as f64 * i16::MAX as f64)) as i16;
-                    last = *byte;
-                    if byte1 < i8::MAX as i16 && (i8::MIN as i16) < byte1 {
-                        println!("pbyte: {byte1}");
-                        let mut byte1 = byte1 as u8;
-                        if byte1 & 1 == 1 {
-                            byte1 -= 1;
+                self.encoded_buffer = self.codecs[self.selected_codec].encode(&self.buffer);
+            }
+            ui.label(format!(
+                "Encoded size: {} bytes ({:.1}%)",
+                self.encoded_buffer.len(),
+                100.0 * self.encoded_buffer.len() as f32
+                    / (self.buffer.len() * std::mem::size_of::<f32>()).max(1) as f32,
+            ));
+
+            if ui.button("Compare Codecs").clicked() {
+                self.codec_sizes = self
+                    .codecs
+                    .iter()
+                    .map(|codec| (codec.name().to_string(), codec.encode(&self.buffer).len()))
+                    .collect();
+            }
+            for (name, size) in &self.codec_sizes {
+                let raw_size = (self.buffer.len() * std::mem::size_of::<f32>()).max(1);
+                ui.label(format!(
+                    "{name}: {size} bytes ({:.1}%)",
+                    100.0 * *size as f32 / raw_size as f32,
+                ));
+            }
+
+            if ui.button("Decode").clicked() {
+                self.buffer = self.codecs[self.selected_codec].decode(&self.encoded_buffer);
+            }
+
+            if ui.button("Clear").clicked() {
+                self.buffer.clear();
+            }
+
+            if ui.button("Load File").clicked() {
+                if let Some(path) = rfd::FileDialog::new()
+                    .add_filter("audio", &["m4a", "ogg", "opus", "mp3"])
+                    .pick_file()
+                {
+                    match decoder::decode_file(&path, self.channels, self.sample_rate) {
+                        Ok(samples) => self.buffer = samples,
+                        Err(error) => eprintln!("Failed to load {}: {error}", path.display()),
+                    }
+                }
+            }
+
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_label("Bit depth")
+                    .selected_text(format!("{}", self.wav_bit_depth))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.wav_bit_depth, 16, "16");
+                        ui.selectable_value(&mut self.wav_bit_depth, 32, "32 (float)");
+                    });
+
+                if ui.button("Export WAV").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("wav", &["wav"])
+                        .save_file()
+                    {
+                        let format = WavFormat {
+                            channels: self.channels,
+                            sample_rate: self.sample_rate,
+                            bit_depth: self.wav_bit_depth,
+                        };
+                        if let Err(error) = wav::write(&path, format, &self.buffer) {
+                            eprintln!("Failed to export {}: {error}", path.display());
                         }
-                        new_buffer.push(byte1)
-                    } else {
-                        println!("nbyte: {byte1}");
-                        let mut bytes = byte1.to_le_bytes();
-                        if bytes[0] & 1 == 0 {
-                            bytes[0] += 1;
+                    }
+                }
+
+                if ui.button("Import WAV").clicked() {
+                    if let Some(path) = rfd::FileDialog::new()
+                        .add_filter("wav", &["wav"])
+                        .pick_file()
+                    {
+                        match wav::read(&path) {
+                            Ok((format, samples)) => {
+                                self.buffer = convert_audio(
+                                    &samples,
+                                    format.channels,
+                                    format.sample_rate,
+                                    self.channels,
+                                    self.sample_rate,
+                                )
+                            }
+                            Err(error) => eprintln!("Failed to import {}: {error}", path.display()),
                         }
-                        new_buffer.extend(bytes);
                     }
                 }
-                println!("Encoded: size {}", new_buffer.len());
-                self.encoded_buffer = new_buffer;
-            }
+            });
 
-            if ui.button("Decode").clicked() {
-                let mut new_buffer = Vec::new();
-                let mut last = 0f32;
-                let mut encoded_buffer = self.encoded_buffer.clone();
-                let mut iter = encoded_buffer.drain(..);
-                while let Some(byte) = iter.next() {
-                    println!("Byte: {byte:8b}");
-                    if byte & 1 == 0 {
-                        if let Some(seccond_byte) = iter.next() {
-                            let byte =
-                                i16::from_le_bytes([byte, seccond_byte]) as f64 / i16::MAX as f64;
-                            let byte = byte + last as f64;
-                            let byte = byte as f32;
-                            last = byte;
-                            new_buffer.push(byte);
+            // Round-trips `encoded_buffer` through disk for verification; the
+            // result is only as faithful as the selected codec's encode/decode
+            // being true inverses of each other.
+            ui.horizontal(|ui| {
+                if ui.button("Dump Encoded").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().save_file() {
+                        if let Err(error) = std::fs::write(&path, &self.encoded_buffer) {
+                            eprintln!("Failed to dump {}: {error}", path.display());
                         }
-                    } else {
-                        let byte = (byte as i8) as f64 / i16::MAX as f64;
-                        let byte = byte + last as f64;
-                        let byte = byte as f32;
-                        last = byte;
-                        new_buffer.push(byte);
                     }
                 }
-                self.buffer = new_buffer;
-                println!(
-                    "Decoded: size {}",
-                    self.buffer.len() * std::mem::size_of::<f32>()
-                );
-            }
 
-            if ui.button("Clear").clicked() {
-                self.buffer.clear();
-            }
+                if ui.button("Load Encoded").clicked() {
+                    if let Some(path) = rfd::FileDialog::new().pick_file() {
+                        match std::fs::read(&path) {
+                            Ok(bytes) => self.encoded_buffer = bytes,
+                            Err(error) => eprintln!("Failed to load {}: {error}", path.display()),
+                        }
+                    }
+                }
+            });
 
             if ui.button("Process").clicked() {
-                let len = (self.buffer.len() as f32 / self.speed).round() as usize;
-                let mut new_buffer = Vec::with_capacity(len);
+                let channels = self.channels as usize;
+                let frame_count = self.buffer.len() / channels.max(1);
+                let len = (frame_count as f32 / self.speed).round() as usize;
+                let mut new_buffer = Vec::with_capacity(len * channels);
                 for i in 0..len {
-                    let original_index = (i as f32 * self.speed).round();
+                    // Linear mode snaps to the nearest source sample (matching
+                    // the original Process behavior); Catmull-Rom needs the
+                    // unrounded position to interpolate between frames.
+                    let original_index = match self.interpolation_mode {
+                        InterpolationMode::Linear => (i as f32 * self.speed).round(),
+                        InterpolationMode::CatmullRom => i as f32 * self.speed,
+                    };
                     let left_index = original_index.floor() as usize;
                     let right_index = left_index + 1;
                     let fractional = original_index.fract();
 
-                    if right_index < self.buffer.len() {
-                        let left_sample = self.buffer[left_index];
-                        let right_sample = self.buffer[right_index];
-
-                        let interpolate_sample =
-                            (1.0 - fractional) * left_sample + fractional * right_sample;
-                        new_buffer.push(interpolate_sample);
-                    } else if left_index < self.buffer.len() {
-                        new_buffer.push(self.buffer[left_index])
+                    if right_index < frame_count {
+                        for c in 0..channels {
+                            let sample = match self.interpolation_mode {
+                                InterpolationMode::Linear => {
+                                    let left_sample = self.buffer[left_index * channels + c];
+                                    let right_sample = self.buffer[right_index * channels + c];
+                                    (1.0 - fractional) * left_sample + fractional * right_sample
+                                }
+                                InterpolationMode::CatmullRom => {
+                                    let p0 =
+                                        self.buffer[left_index.saturating_sub(1) * channels + c];
+                                    let p1 = self.buffer[left_index * channels + c];
+                                    let p2 = self.buffer[right_index * channels + c];
+                                    let p3 = self.buffer
+                                        [(right_index + 1).min(frame_count - 1) * channels + c];
+                                    catmull_rom(p0, p1, p2, p3, fractional)
+                                }
+                            };
+                            new_buffer.push(sample);
+                        }
+                    } else if left_index < frame_count {
+                        for c in 0..channels {
+                            new_buffer.push(self.buffer[left_index * channels + c]);
+                        }
                     }
                 }
                 self.buffer = new_buffer;
             }
 
+            ui.horizontal(|ui| {
+                ui.add(egui::DragValue::new(&mut self.intro_start).prefix("Intro Start: "));
+                ui.add(egui::DragValue::new(&mut self.loop_start).prefix("Loop Start: "));
+                ui.checkbox(&mut self.loop_enabled, "Loop");
+            });
+
             if ui.button("Submit").clicked() {
-                let _ = self.output_sender.send(self.buffer.clone());
-                self.rem += 1;
+                if self.loop_enabled {
+                    let channels = self.channels.max(1) as usize;
+                    let frame_count = self.buffer.len() / channels;
+                    let intro_start = self.intro_start.min(frame_count);
+                    self.playback = Some(PlaybackState {
+                        playing_intro: intro_start < self.loop_start.min(frame_count),
+                        position: intro_start,
+                    });
+                } else {
+                    self.playback = None;
+                    self.output_buffers
+                        .lock()
+                        .unwrap()
+                        .produce(self.buffer.clone());
+                }
+            }
+
+            if ui.button("Stop").clicked() {
+                self.playback = None;
             }
 
             ui.horizontal(|ui| {
@@ -267,24 +508,32 @@ impl App for Application {
                     .speed(1),
             );
             egui::plot::Plot::new("Buffer").show(ui, |ui| {
+                let channels = self.channels as usize;
+                let frame_count = self.buffer.len() / channels.max(1);
                 let bounds = ui.plot_bounds();
-                let range = bounds.min()[0].max(0.0)..bounds.max()[0].min(self.buffer.len() as f64);
-                ui.line(egui::plot::Line::new(
-                    egui::plot::PlotPoints::from_parametric_callback(
-                        |t| {
-                            let i = t as usize;
-                            if self.buffer.len() > i {
-                                (t, self.buffer[i] as f64)
-                            } else {
-                                (0.0, 0.0)
-                            }
-                        },
-                        range,
-                        self.resolution.min(self.buffer.len()),
-                    ),
-                ));
+                let range = bounds.min()[0].max(0.0)..bounds.max()[0].min(frame_count as f64);
+                for c in 0..channels {
+                    ui.line(
+                        egui::plot::Line::new(egui::plot::PlotPoints::from_parametric_callback(
+                            |t| {
+                                let i = t as usize;
+                                if frame_count > i {
+                                    (t, self.buffer[i * channels + c] as f64)
+                                } else {
+                                    (0.0, 0.0)
+                                }
+                            },
+                            range.clone(),
+                            self.resolution.min(frame_count),
+                        ))
+                        .name(format!("Channel {c}")),
+                    );
+                }
                 ui.vline(
-                    egui::plot::VLine::new(self.buffer.len() as f32 - self.rem as f32).name("Rem"),
+                    egui::plot::VLine::new(
+                        frame_count as f32 - (self.rem / channels.max(1)) as f32,
+                    )
+                    .name("Rem"),
                 )
             });
         });
@@ -301,7 +550,73 @@ impl Drop for Application {
     }
 }
 
+/// Converts interleaved `input` from `src_channels`/`src_rate` to
+/// `dst_channels`/`dst_rate`, so recordings from a device whose native
+/// layout differs from the working buffer's can still be appended to it.
+fn convert_audio(
+    input: &[f32],
+    src_channels: u16,
+    src_rate: u32,
+    dst_channels: u16,
+    dst_rate: u32,
+) -> Vec<f32> {
+    let src_channels = src_channels as usize;
+    let dst_channels = dst_channels as usize;
+    if src_channels == 0 || dst_channels == 0 {
+        return Vec::new();
+    }
+
+    let src_frames = input.len() / src_channels;
+
+    // Remap channel count first: downmix to mono by averaging, or
+    // duplicate a mono source across every destination channel.
+    let remapped: Vec<f32> = (0..src_frames)
+        .flat_map(|frame| {
+            let base = frame * src_channels;
+            let mixed = input[base..base + src_channels].iter().sum::<f32>() / src_channels as f32;
+            (0..dst_channels).map(move |c| {
+                if src_channels == dst_channels {
+                    input[base + c]
+                } else {
+                    mixed
+                }
+            })
+        })
+        .collect();
+
+    if src_rate == dst_rate || src_frames == 0 {
+        return remapped;
+    }
+
+    // Linear-interpolate between frames to convert the sample rate.
+    let dst_frames = ((src_frames as f64 * dst_rate as f64) / src_rate as f64).round() as usize;
+    let mut out = Vec::with_capacity(dst_frames * dst_channels);
+    for i in 0..dst_frames {
+        let original_index = i as f64 * src_rate as f64 / dst_rate as f64;
+        let left_index = original_index.floor() as usize;
+        let right_index = (left_index + 1).min(src_frames - 1);
+        let fractional = (original_index - left_index as f64) as f32;
+
+        for c in 0..dst_channels {
+            let left_sample = remapped[left_index * dst_channels + c];
+            let right_sample = remapped[right_index * dst_channels + c];
+            out.push((1.0 - fractional) * left_sample + fractional * right_sample);
+        }
+    }
+    out
+}
+
+fn catmull_rom(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
+    let t2 = t * t;
+    let t3 = t2 * t;
+    0.5 * (2.0 * p1
+        + (-p0 + p2) * t
+        + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t2
+        + (-p0 + 3.0 * p1 - 3.0 * p2 + p3) * t3)
+}
+
 fn main() {
+    ffmpeg_next::init().expect("failed to initialize ffmpeg");
     eframe::run_native(
         "codec-map",
         eframe::NativeOptions::default(),
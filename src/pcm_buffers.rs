@@ -0,0 +1,52 @@
+/// A producer/consumer queue of PCM chunks shared between the UI thread
+/// (which pushes decoded/processed audio) and the output stream's audio
+/// callback (which drains it sample-by-sample).
+///
+/// This avoids the per-sample `pop_front` realloc churn of a flat
+/// `VecDeque<f32>` by keeping whole chunks and only tracking a cursor into
+/// the front one.
+#[derive(Default)]
+pub struct PcmBuffers {
+    chunks: Vec<Vec<f32>>,
+    consumer_cursor: usize,
+}
+
+impl PcmBuffers {
+    /// Total number of samples still queued, across all chunks.
+    pub fn samples_available(&self) -> usize {
+        let front_remaining = self
+            .chunks
+            .first()
+            .map(|chunk| chunk.len() - self.consumer_cursor)
+            .unwrap_or(0);
+        front_remaining + self.chunks.iter().skip(1).map(Vec::len).sum::<usize>()
+    }
+
+    /// Queues a chunk of samples to be played back. Empty chunks are ignored.
+    pub fn produce(&mut self, chunk: Vec<f32>) {
+        if !chunk.is_empty() {
+            self.chunks.push(chunk);
+        }
+    }
+
+    /// Fills `out` with the next `out.len()` queued samples, popping
+    /// exhausted chunks as it goes. Returns `false` without writing
+    /// anything if fewer samples than requested are available.
+    pub fn consume_exact(&mut self, out: &mut [f32]) -> bool {
+        if self.samples_available() < out.len() {
+            return false;
+        }
+
+        for sample in out.iter_mut() {
+            let chunk = &self.chunks[0];
+            *sample = chunk[self.consumer_cursor];
+            self.consumer_cursor += 1;
+            if self.consumer_cursor >= chunk.len() {
+                self.chunks.remove(0);
+                self.consumer_cursor = 0;
+            }
+        }
+
+        true
+    }
+}
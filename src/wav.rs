@@ -0,0 +1,149 @@
+use std::{fmt, fs, io, path::Path};
+
+/// Describes the PCM layout a WAV file is written with/expected to hold.
+#[derive(Debug, Clone, Copy)]
+pub struct WavFormat {
+    pub channels: u16,
+    pub sample_rate: u32,
+    pub bit_depth: u16,
+}
+
+#[derive(Debug)]
+pub enum WavError {
+    Io(io::Error),
+    NotRiffWave,
+    MissingChunk(&'static str),
+    MalformedChunk(&'static str),
+    UnsupportedBitDepth(u16),
+}
+
+impl fmt::Display for WavError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WavError::Io(error) => write!(f, "io error: {error}"),
+            WavError::NotRiffWave => write!(f, "not a RIFF/WAVE file"),
+            WavError::MissingChunk(name) => write!(f, "missing \"{name}\" chunk"),
+            WavError::MalformedChunk(name) => write!(f, "\"{name}\" chunk is too short"),
+            WavError::UnsupportedBitDepth(bits) => write!(f, "unsupported bit depth: {bits}"),
+        }
+    }
+}
+
+impl std::error::Error for WavError {}
+
+impl From<io::Error> for WavError {
+    fn from(error: io::Error) -> Self {
+        WavError::Io(error)
+    }
+}
+
+/// Writes `samples` as a RIFF/WAVE file at `path`, encoded at
+/// `format.bit_depth` (16-bit PCM or 32-bit float).
+pub fn write(path: &Path, format: WavFormat, samples: &[f32]) -> Result<(), WavError> {
+    let data = match format.bit_depth {
+        16 => samples
+            .iter()
+            .flat_map(|sample| {
+                let quantized = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+                quantized.to_le_bytes()
+            })
+            .collect::<Vec<u8>>(),
+        32 => samples
+            .iter()
+            .flat_map(|sample| sample.to_le_bytes())
+            .collect::<Vec<u8>>(),
+        bits => return Err(WavError::UnsupportedBitDepth(bits)),
+    };
+
+    let is_float = format.bit_depth == 32;
+    let byte_rate = format.sample_rate * format.channels as u32 * (format.bit_depth as u32 / 8);
+    let block_align = format.channels * (format.bit_depth / 8);
+
+    let mut bytes = Vec::with_capacity(44 + data.len());
+    bytes.extend(b"RIFF");
+    bytes.extend(((36 + data.len()) as u32).to_le_bytes());
+    bytes.extend(b"WAVE");
+
+    bytes.extend(b"fmt ");
+    bytes.extend(16u32.to_le_bytes());
+    bytes.extend((if is_float { 3u16 } else { 1u16 }).to_le_bytes());
+    bytes.extend(format.channels.to_le_bytes());
+    bytes.extend(format.sample_rate.to_le_bytes());
+    bytes.extend(byte_rate.to_le_bytes());
+    bytes.extend(block_align.to_le_bytes());
+    bytes.extend(format.bit_depth.to_le_bytes());
+
+    bytes.extend(b"data");
+    bytes.extend((data.len() as u32).to_le_bytes());
+    bytes.extend(data);
+
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+/// Reads a RIFF/WAVE file at `path`, returning its samples as f32 alongside
+/// the format it was stored in.
+pub fn read(path: &Path) -> Result<(WavFormat, Vec<f32>), WavError> {
+    let bytes = fs::read(path)?;
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        return Err(WavError::NotRiffWave);
+    }
+
+    let mut channels = None;
+    let mut sample_rate = None;
+    let mut bit_depth = None;
+    let mut is_float = false;
+    let mut data = None;
+
+    let mut offset = 12;
+    while offset + 8 <= bytes.len() {
+        let id = &bytes[offset..offset + 4];
+        let size = u32::from_le_bytes(bytes[offset + 4..offset + 8].try_into().unwrap()) as usize;
+        let body_start = offset + 8;
+        let body = &bytes[body_start..(body_start + size).min(bytes.len())];
+
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    return Err(WavError::MalformedChunk("fmt "));
+                }
+                let format_tag = u16::from_le_bytes(body[0..2].try_into().unwrap());
+                is_float = format_tag == 3;
+                channels = Some(u16::from_le_bytes(body[2..4].try_into().unwrap()));
+                sample_rate = Some(u32::from_le_bytes(body[4..8].try_into().unwrap()));
+                bit_depth = Some(u16::from_le_bytes(body[14..16].try_into().unwrap()));
+            }
+            b"data" => data = Some(body.to_vec()),
+            _ => {}
+        }
+
+        // Chunks are word-aligned; skip the padding byte for odd sizes.
+        offset = body_start + size + (size & 1);
+    }
+
+    let channels = channels.ok_or(WavError::MissingChunk("fmt "))?;
+    let sample_rate = sample_rate.ok_or(WavError::MissingChunk("fmt "))?;
+    let bit_depth = bit_depth.ok_or(WavError::MissingChunk("fmt "))?;
+    let data = data.ok_or(WavError::MissingChunk("data"))?;
+
+    let samples = match (bit_depth, is_float) {
+        (16, false) => data
+            .chunks_exact(2)
+            .map(|bytes| i16::from_le_bytes(bytes.try_into().unwrap()) as f32 / i16::MAX as f32)
+            .collect(),
+        (32, true) => data
+            .chunks_exact(4)
+            .map(|bytes| f32::from_le_bytes(bytes.try_into().unwrap()))
+            .collect(),
+        (bits, _) => return Err(WavError::UnsupportedBitDepth(bits)),
+    };
+
+    Ok((
+        WavFormat {
+            channels,
+            sample_rate,
+            bit_depth,
+        },
+        samples,
+    ))
+}